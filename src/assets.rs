@@ -0,0 +1,87 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use directories::ProjectDirs;
+use syntect::dumps::{dump_to_file, from_dump_file};
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::SyntaxSet;
+
+/// Delta's config directory (e.g. `~/.config/delta` on Linux), if the platform provides one.
+fn config_dir() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "delta").map(|dirs| dirs.config_dir().to_path_buf())
+}
+
+fn syntax_set_cache_path(config_dir: &Path) -> PathBuf {
+    config_dir.join("syntaxes.bin")
+}
+
+fn theme_set_cache_path(config_dir: &Path) -> PathBuf {
+    config_dir.join("themes.bin")
+}
+
+/// Load the combined default + user `SyntaxSet`/`ThemeSet`, from a cache unless `build_cache` is set.
+pub fn load(build_cache: bool) -> (SyntaxSet, ThemeSet) {
+    match config_dir() {
+        Some(config_dir) => load_with_config_dir(&config_dir, build_cache),
+        None => (SyntaxSet::load_defaults_newlines(), ThemeSet::load_defaults()),
+    }
+}
+
+fn load_with_config_dir(config_dir: &Path, build_cache: bool) -> (SyntaxSet, ThemeSet) {
+    let syntax_set_path = syntax_set_cache_path(config_dir);
+    let theme_set_path = theme_set_cache_path(config_dir);
+
+    if !build_cache {
+        if let (Ok(syntax_set), Ok(theme_set)) = (
+            from_dump_file(&syntax_set_path),
+            from_dump_file(&theme_set_path),
+        ) {
+            return (syntax_set, theme_set);
+        }
+    }
+
+    let syntax_set = build_syntax_set(config_dir);
+    let theme_set = build_theme_set(config_dir);
+
+    if fs::create_dir_all(config_dir).is_ok() {
+        let _ = dump_to_file(&syntax_set, &syntax_set_path);
+        let _ = dump_to_file(&theme_set, &theme_set_path);
+    }
+
+    (syntax_set, theme_set)
+}
+
+fn build_syntax_set(config_dir: &Path) -> SyntaxSet {
+    let mut builder = SyntaxSet::load_defaults_newlines().into_builder();
+    let _ = builder.add_from_folder(config_dir, true);
+    builder.build()
+}
+
+fn build_theme_set(config_dir: &Path) -> ThemeSet {
+    let mut theme_set = ThemeSet::load_defaults();
+    if let Ok(user_theme_set) = ThemeSet::load_from_folder(config_dir) {
+        theme_set.themes.extend(user_theme_set.themes);
+    }
+    theme_set
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn syntax_set_cache_path_is_under_config_dir() {
+        assert_eq!(
+            syntax_set_cache_path(Path::new("/home/user/.config/delta")),
+            Path::new("/home/user/.config/delta/syntaxes.bin")
+        );
+    }
+
+    #[test]
+    fn theme_set_cache_path_is_under_config_dir() {
+        assert_eq!(
+            theme_set_cache_path(Path::new("/home/user/.config/delta")),
+            Path::new("/home/user/.config/delta/themes.bin")
+        );
+    }
+}
@@ -1,8 +1,10 @@
 extern crate structopt;
 
+mod assets;
 mod parse;
 
-use std::io::{self, BufRead, ErrorKind};
+use std::env;
+use std::io::{self, BufRead, ErrorKind, Write};
 use std::process;
 
 use console::strip_ansi_codes;
@@ -11,33 +13,215 @@ use syntect::easy::HighlightLines;
 use syntect::highlighting::{Color, Style, ThemeSet};
 use syntect::parsing::{SyntaxReference, SyntaxSet};
 
-pub const DELTA_THEME_DEFAULT: &str = "base16-mocha.dark";
+pub const DELTA_THEME_DEFAULT_DARK: &str = "base16-mocha.dark";
+pub const DELTA_THEME_DEFAULT_LIGHT: &str = "InspiredGitHub";
 
-const GREEN: Color = Color {
+const GREEN_DARK: Color = Color {
     r: 0x01,
     g: 0x18,
     b: 0x00,
     a: 0x00,
 };
 
-const RED: Color = Color {
+const RED_DARK: Color = Color {
     r: 0x24,
     g: 0x00,
     b: 0x01,
     a: 0x00,
 };
 
+const GREEN_DARK_EMPH: Color = Color {
+    r: 0x02,
+    g: 0x44,
+    b: 0x00,
+    a: 0x00,
+};
+
+const RED_DARK_EMPH: Color = Color {
+    r: 0x66,
+    g: 0x00,
+    b: 0x02,
+    a: 0x00,
+};
+
+const GREEN_LIGHT: Color = Color {
+    r: 0xd4,
+    g: 0xf8,
+    b: 0xd4,
+    a: 0x00,
+};
+
+const RED_LIGHT: Color = Color {
+    r: 0xf8,
+    g: 0xd4,
+    b: 0xd4,
+    a: 0x00,
+};
+
+const GREEN_LIGHT_EMPH: Color = Color {
+    r: 0x9a,
+    g: 0xf0,
+    b: 0x9a,
+    a: 0x00,
+};
+
+const RED_LIGHT_EMPH: Color = Color {
+    r: 0xf0,
+    g: 0x9a,
+    b: 0x9a,
+    a: 0x00,
+};
+
+/// The normal and emphasized backgrounds used to paint added/removed diff lines.
+struct Colors {
+    plus: Color,
+    minus: Color,
+    plus_emph: Color,
+    minus_emph: Color,
+}
+
+/// Return the diff colors appropriate for a light or dark terminal background.
+fn get_colors(light: bool) -> Colors {
+    if light {
+        Colors {
+            plus: GREEN_LIGHT,
+            minus: RED_LIGHT,
+            plus_emph: GREEN_LIGHT_EMPH,
+            minus_emph: RED_LIGHT_EMPH,
+        }
+    } else {
+        Colors {
+            plus: GREEN_DARK,
+            minus: RED_DARK,
+            plus_emph: GREEN_DARK_EMPH,
+            minus_emph: RED_DARK_EMPH,
+        }
+    }
+}
+
+/// Width, in characters, of each line-number column in the `--line-numbers` gutter.
+const PANEL_WIDTH: usize = 4;
+
+const GUTTER_FOREGROUND: Color = Color {
+    r: 0x6c,
+    g: 0x6c,
+    b: 0x6c,
+    a: 0x00,
+};
+
+/// Render the `--line-numbers` gutter for a hunk line, blank on a side with no line number.
+fn format_gutter(old_line_number: Option<usize>, new_line_number: Option<usize>, color_mode: ColorMode) -> String {
+    let old = old_line_number.map(|n| n.to_string()).unwrap_or_default();
+    let new = new_line_number.map(|n| n.to_string()).unwrap_or_default();
+    let text = format!("{:>w$}│{:>w$}│ ", old, new, w = PANEL_WIDTH);
+    let mut buf = String::new();
+    paint(&text, Some(GUTTER_FOREGROUND), None, color_mode, true, &mut buf);
+    buf
+}
+
+/// Which syntect theme to load: `--theme`, else `--light`/`--dark`, else the default.
+fn get_theme_name(opt: &Opt) -> &str {
+    match &opt.theme {
+        Some(theme) => theme,
+        None if opt.light => DELTA_THEME_DEFAULT_LIGHT,
+        None => DELTA_THEME_DEFAULT_DARK,
+    }
+}
+
+/// Whether to use light-background diff colors: `--light`/`--dark` if given, else inferred from the theme.
+fn resolve_light(opt: &Opt, theme: &syntect::highlighting::Theme) -> bool {
+    if opt.light {
+        true
+    } else if opt.dark {
+        false
+    } else {
+        theme_is_light(theme)
+    }
+}
+
+/// Guess whether a theme is meant for a light background, from its `background` setting's luminance.
+fn theme_is_light(theme: &syntect::highlighting::Theme) -> bool {
+    match theme.settings.background {
+        Some(color) => {
+            let luminance =
+                0.2126 * color.r as f64 + 0.7152 * color.g as f64 + 0.0722 * color.b as f64;
+            luminance > 127.0
+        }
+        None => false,
+    }
+}
+
+/// The color capability of the terminal delta is writing to.
+#[derive(PartialEq, Clone, Copy)]
+enum ColorMode {
+    TrueColor,
+    Xterm256,
+    None,
+}
+
+/// The color mode to use: `--color`, else `COLORTERM`, else 256-color.
+fn get_color_mode(opt: &Opt) -> ColorMode {
+    match opt.color.as_ref().map(String::as_str) {
+        Some("truecolor") => ColorMode::TrueColor,
+        Some("256") => ColorMode::Xterm256,
+        Some("never") => ColorMode::None,
+        _ => match env::var("COLORTERM") {
+            Ok(value) if value == "truecolor" || value == "24bit" => ColorMode::TrueColor,
+            _ => ColorMode::Xterm256,
+        },
+    }
+}
+
+/// Downsample a 24-bit `Color` to the nearest xterm-256 index (6x6x6 cube plus grayscale ramp).
+fn downsample_to_256(color: Color) -> u8 {
+    let to_cube_index = |c: u8| -> u8 { (((c as i32 - 35).max(0) as f64 / 40.0).round() as u8).min(5) };
+    let r = to_cube_index(color.r);
+    let g = to_cube_index(color.g);
+    let b = to_cube_index(color.b);
+    let cube_index = 16 + 36 * r + 6 * g + b;
+    let cube_value = |c: u8| -> u8 {
+        if c == 0 {
+            0
+        } else {
+            55 + c * 40
+        }
+    };
+    let cube_distance = {
+        let dr = color.r as i32 - cube_value(r) as i32;
+        let dg = color.g as i32 - cube_value(g) as i32;
+        let db = color.b as i32 - cube_value(b) as i32;
+        dr * dr + dg * dg + db * db
+    };
+
+    let average = (color.r as u32 + color.g as u32 + color.b as u32) / 3;
+    let gray_index = (((average as i32 - 3) / 10).max(0) as u8).min(23);
+    let gray_value = 8 + gray_index * 10;
+    let gray_distance = {
+        let dr = color.r as i32 - gray_value as i32;
+        let dg = color.g as i32 - gray_value as i32;
+        let db = color.b as i32 - gray_value as i32;
+        dr * dr + dg * dg + db * db
+    };
+
+    if gray_distance < cube_distance {
+        232 + gray_index
+    } else {
+        cube_index
+    }
+}
+
 #[derive(StructOpt, Debug)]
 #[structopt(name = "delta")]
 struct Opt {
     /// Use diff highlighting colors appropriate for a light terminal
     /// background
-    #[structopt(long = "light")]
+    #[structopt(long = "light", conflicts_with = "dark")]
     light: bool,
 
     /// Use diff highlighting colors appropriate for a dark terminal
-    /// background
-    #[structopt(long = "dark")]
+    /// background (this is the default, unless `--theme` names a theme
+    /// with a light background)
+    #[structopt(long = "dark", conflicts_with = "light")]
     dark: bool,
 
     /// The width (in characters) of the diff highlighting. By
@@ -45,6 +229,91 @@ struct Opt {
     /// each line
     #[structopt(short = "-w", long = "width")]
     width: Option<u16>,
+
+    /// The syntect theme to use for syntax highlighting and diff colors.
+    /// Use `--list-themes` to see the available options.
+    #[structopt(long = "theme")]
+    theme: Option<String>,
+
+    /// List all available syntect themes, with a sample diff highlighted
+    /// in each, and exit
+    #[structopt(long = "list-themes")]
+    list_themes: bool,
+
+    /// The color capability of the terminal: "truecolor" for 24-bit
+    /// colors, "256" for xterm-256 colors, or "never" to disable colors
+    /// entirely. By default this is detected from the COLORTERM
+    /// environment variable
+    #[structopt(long = "color")]
+    color: Option<String>,
+
+    /// Whether to use a pager when displaying output: "always" or
+    /// "never", or "auto" to page only when stdout is a terminal
+    #[structopt(long = "paging", default_value = "auto")]
+    paging: String,
+
+    /// Show the old and new line numbers of each hunk line in a gutter
+    /// to the left of the code
+    #[structopt(long = "line-numbers")]
+    line_numbers: bool,
+
+    /// Rebuild delta's cache of syntaxes and themes from the defaults plus
+    /// any user `.sublime-syntax`/`.tmTheme` files in its config directory,
+    /// instead of loading a previously-built cache
+    #[structopt(long = "build-cache")]
+    build_cache: bool,
+}
+
+/// Where delta's highlighted output goes: a pager, or straight to stdout.
+enum OutputType {
+    Pager(process::Child),
+    Stdout(io::Stdout),
+}
+
+impl OutputType {
+    /// The output sink for `paging_mode` ("always"/"never"/"auto"), falling back to stdout.
+    fn from_mode(paging_mode: &str) -> io::Result<Self> {
+        let use_pager = match paging_mode {
+            "always" => true,
+            "never" => false,
+            _ => console::user_attended(),
+        };
+        if !use_pager {
+            return Ok(OutputType::Stdout(io::stdout()));
+        }
+        let pager_cmd = env::var("DELTA_PAGER")
+            .or_else(|_| env::var("PAGER"))
+            .unwrap_or_else(|_| "less".to_string());
+        match make_pager_command(&pager_cmd)
+            .stdin(process::Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => Ok(OutputType::Pager(child)),
+            Err(_) => Ok(OutputType::Stdout(io::stdout())),
+        }
+    }
+
+    fn handle(&mut self) -> io::Result<&mut dyn Write> {
+        Ok(match *self {
+            OutputType::Pager(ref mut child) => child
+                .stdin
+                .as_mut()
+                .expect("Could not open stdin for pager"),
+            OutputType::Stdout(ref mut handle) => handle,
+        })
+    }
+}
+
+/// Build the pager command, adding delta's preferred flags when it's `less`.
+fn make_pager_command(pager_cmd: &str) -> process::Command {
+    let mut parts = pager_cmd.split_whitespace();
+    let program = parts.next().unwrap_or("less");
+    let mut command = process::Command::new(program);
+    command.args(parts);
+    if program.ends_with("less") {
+        command.args(&["--quit-if-one-screen", "--RAW-CONTROL-CHARS", "--no-init"]);
+    }
+    command
 }
 
 #[derive(PartialEq)]
@@ -68,61 +337,470 @@ fn main() {
 }
 
 fn delta() -> std::io::Result<()> {
-    use std::io::Write;
-    let syntax_set = SyntaxSet::load_defaults_newlines();
-    let theme_set = ThemeSet::load_defaults();
-    let theme = &theme_set.themes[DELTA_THEME_DEFAULT];
-    let mut output = String::new();
+    let opt = Opt::from_args();
+    let (syntax_set, theme_set) = assets::load(opt.build_cache);
+
+    if opt.list_themes {
+        return list_themes(&syntax_set, &theme_set, get_color_mode(&opt));
+    }
+
+    let theme_name = get_theme_name(&opt);
+    let theme = match theme_set.themes.get(theme_name) {
+        Some(theme) => theme,
+        None => {
+            eprintln!(
+                "No such theme: {:?}. Run `delta --list-themes` to see the available themes.",
+                theme_name
+            );
+            process::exit(1);
+        }
+    };
+    let colors = get_colors(resolve_light(&opt, theme));
+    let color_mode = get_color_mode(&opt);
     let mut state = State::Unknown;
     let mut syntax: Option<&SyntaxReference> = None;
+    let mut tried_first_line_syntax = false;
+    let mut hunk_starts_at_first_line = false;
     let mut did_emit_line: bool;
+    let mut minus_buffer: Vec<(usize, String)> = Vec::new();
+    let mut plus_buffer: Vec<(usize, String)> = Vec::new();
+    let mut old_line_number = 0;
+    let mut new_line_number = 0;
     let stdin = io::stdin();
-    let mut stdout = io::stdout();
-    let opt = Opt::from_args();
+    let mut output_type = OutputType::from_mode(&opt.paging)?;
 
-    for _line in stdin.lock().lines() {
-        let raw_line = _line?;
-        let mut line = strip_ansi_codes(&raw_line).to_string();
-        did_emit_line = false;
-        if line.starts_with("diff --") {
-            state = State::DiffMeta;
-            syntax = match parse::get_file_extension_from_diff_line(&line) {
-                Some(extension) => syntax_set.find_syntax_by_extension(extension),
-                None => None,
-            };
-        } else if line.starts_with("commit") {
-            state = State::Commit;
-        } else if line.starts_with("@@") {
-            state = State::DiffHunk;
-        } else if state == State::DiffHunk {
-            match syntax {
-                Some(syntax) => {
-                    let mut highlighter = HighlightLines::new(syntax, theme);
-                    let first_char = line.chars().next();
-                    let background_color = match first_char {
-                        Some('+') => Some(GREEN),
-                        Some('-') => Some(RED),
-                        _ => None,
+    {
+        let writer = output_type.handle()?;
+        for _line in stdin.lock().lines() {
+            let raw_line = _line?;
+            let line = strip_ansi_codes(&raw_line).to_string();
+            did_emit_line = false;
+            let first_char = line.chars().next();
+
+            if line.starts_with("diff --") {
+                flush_line_pairs(
+                    &mut minus_buffer,
+                    &mut plus_buffer,
+                    syntax,
+                    theme,
+                    &syntax_set,
+                    &colors,
+                    color_mode,
+                    opt.line_numbers,
+                    writer,
+                )?;
+                state = State::DiffMeta;
+                syntax = parse::get_file_name_from_diff_line(&line).and_then(|file_name| {
+                    syntax_set.find_syntax_by_extension(file_name).or_else(|| {
+                        parse::get_extension(file_name)
+                            .and_then(|extension| syntax_set.find_syntax_by_extension(extension))
+                    })
+                });
+                tried_first_line_syntax = false;
+            } else if line.starts_with("commit") {
+                flush_line_pairs(
+                    &mut minus_buffer,
+                    &mut plus_buffer,
+                    syntax,
+                    theme,
+                    &syntax_set,
+                    &colors,
+                    color_mode,
+                    opt.line_numbers,
+                    writer,
+                )?;
+                state = State::Commit;
+            } else if line.starts_with("@@") {
+                flush_line_pairs(
+                    &mut minus_buffer,
+                    &mut plus_buffer,
+                    syntax,
+                    theme,
+                    &syntax_set,
+                    &colors,
+                    color_mode,
+                    opt.line_numbers,
+                    writer,
+                )?;
+                state = State::DiffHunk;
+                hunk_starts_at_first_line = false;
+                if let Some((old_start, _, new_start, _)) = parse::parse_hunk_header(&line) {
+                    old_line_number = old_start;
+                    new_line_number = new_start;
+                    hunk_starts_at_first_line = old_start <= 1 && new_start <= 1;
+                }
+            } else if state == State::DiffHunk {
+                if syntax.is_none() && !tried_first_line_syntax && hunk_starts_at_first_line {
+                    let content = match first_char {
+                        Some('+') | Some('-') => &line[1..],
+                        _ => line.as_str(),
                     };
-                    if first_char == Some('+') || first_char == Some('-') {
-                        line = line[1..].to_string();
-                        output.push_str(" ");
+                    syntax = syntax_set.find_syntax_by_first_line(content);
+                    tried_first_line_syntax = true;
+                }
+                match (first_char, syntax) {
+                    (Some('-'), Some(_)) => {
+                        minus_buffer.push((old_line_number, line[1..].to_string()));
+                        old_line_number += 1;
+                        did_emit_line = true;
                     }
-                    if line.len() < 100 {
-                        line = format!("{}{}", line, " ".repeat(100 - line.len()));
+                    (Some('+'), Some(_)) => {
+                        plus_buffer.push((new_line_number, line[1..].to_string()));
+                        new_line_number += 1;
+                        did_emit_line = true;
                     }
-                    let ranges: Vec<(Style, &str)> = highlighter.highlight(&line, &syntax_set);
-                    paint_ranges(&ranges[..], background_color, &mut output);
-                    writeln!(stdout, "{}", output)?;
-                    output.truncate(0);
-                    did_emit_line = true;
+                    (_, Some(current_syntax)) => {
+                        flush_line_pairs(
+                            &mut minus_buffer,
+                            &mut plus_buffer,
+                            syntax,
+                            theme,
+                            &syntax_set,
+                            &colors,
+                            color_mode,
+                            opt.line_numbers,
+                            writer,
+                        )?;
+                        let formatted =
+                            highlight_line(&line, current_syntax, theme, &syntax_set, None, color_mode);
+                        let gutter = if opt.line_numbers {
+                            format_gutter(Some(old_line_number), Some(new_line_number), color_mode)
+                        } else {
+                            String::new()
+                        };
+                        old_line_number += 1;
+                        new_line_number += 1;
+                        writeln!(writer, "{}{}", gutter, formatted)?;
+                        did_emit_line = true;
+                    }
+                    (_, None) => {}
                 }
-                None => (),
             }
+            if !did_emit_line {
+                writeln!(writer, "{}", raw_line)?;
+            }
+        }
+        flush_line_pairs(
+            &mut minus_buffer,
+            &mut plus_buffer,
+            syntax,
+            theme,
+            &syntax_set,
+            &colors,
+            color_mode,
+            opt.line_numbers,
+            writer,
+        )?;
+    }
+
+    if let OutputType::Pager(mut child) = output_type {
+        drop(child.stdin.take());
+        child.wait()?;
+    }
+    Ok(())
+}
+
+/// Flush the buffered minus/plus lines, pairing them up for word-level diffing.
+fn flush_line_pairs(
+    minus_buffer: &mut Vec<(usize, String)>,
+    plus_buffer: &mut Vec<(usize, String)>,
+    syntax: Option<&SyntaxReference>,
+    theme: &syntect::highlighting::Theme,
+    syntax_set: &SyntaxSet,
+    colors: &Colors,
+    color_mode: ColorMode,
+    line_numbers: bool,
+    writer: &mut dyn Write,
+) -> io::Result<()> {
+    if minus_buffer.is_empty() && plus_buffer.is_empty() {
+        return Ok(());
+    }
+    let syntax = match syntax {
+        Some(syntax) => syntax,
+        None => {
+            minus_buffer.clear();
+            plus_buffer.clear();
+            return Ok(());
         }
-        if !did_emit_line {
-            writeln!(stdout, "{}", raw_line)?;
+    };
+    let gutter = |old: Option<usize>, new: Option<usize>| {
+        if line_numbers {
+            format_gutter(old, new, color_mode)
+        } else {
+            String::new()
         }
+    };
+
+    let paired = minus_buffer.len().min(plus_buffer.len());
+    for i in 0..paired {
+        let (old_line_number, minus_text) = &minus_buffer[i];
+        let (new_line_number, plus_text) = &plus_buffer[i];
+        let (minus_line, plus_line) = format_word_diff_pair(
+            minus_text,
+            plus_text,
+            syntax,
+            theme,
+            syntax_set,
+            colors,
+            color_mode,
+        );
+        writeln!(writer, "{} {}", gutter(Some(*old_line_number), None), minus_line)?;
+        writeln!(writer, "{} {}", gutter(None, Some(*new_line_number)), plus_line)?;
+    }
+    for (old_line_number, minus_line) in &minus_buffer[paired..] {
+        let formatted = highlight_line(
+            minus_line,
+            syntax,
+            theme,
+            syntax_set,
+            Some(colors.minus),
+            color_mode,
+        );
+        writeln!(writer, "{} {}", gutter(Some(*old_line_number), None), formatted)?;
+    }
+    for (new_line_number, plus_line) in &plus_buffer[paired..] {
+        let formatted = highlight_line(
+            plus_line,
+            syntax,
+            theme,
+            syntax_set,
+            Some(colors.plus),
+            color_mode,
+        );
+        writeln!(writer, "{} {}", gutter(None, Some(*new_line_number)), formatted)?;
+    }
+
+    minus_buffer.clear();
+    plus_buffer.clear();
+    Ok(())
+}
+
+/// Highlight a single line with a flat background color.
+fn highlight_line(
+    line: &str,
+    syntax: &SyntaxReference,
+    theme: &syntect::highlighting::Theme,
+    syntax_set: &SyntaxSet,
+    background_color: Option<Color>,
+    color_mode: ColorMode,
+) -> String {
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    let padded_line = pad_line(line);
+    let ranges: Vec<(Style, &str)> = highlighter.highlight(&padded_line, syntax_set);
+    let mut output = String::new();
+    paint_ranges(&ranges[..], background_color, color_mode, &mut output);
+    output
+}
+
+/// Pad a line out to delta's fixed highlighting width.
+fn pad_line(line: &str) -> String {
+    if line.len() < 100 {
+        format!("{}{}", line, " ".repeat(100 - line.len()))
+    } else {
+        line.to_string()
+    }
+}
+
+/// Word-character class used to tokenize a line for word-level diffing.
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Split a line into `(start, end)` byte ranges of maximal word/non-word runs.
+fn tokenize(line: &str) -> Vec<(usize, usize)> {
+    let mut tokens = Vec::new();
+    let mut chars = line.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        let word = is_word_char(c);
+        let mut end = start + c.len_utf8();
+        chars.next();
+        while let Some(&(idx, next_c)) = chars.peek() {
+            if is_word_char(next_c) == word {
+                end = idx + next_c.len_utf8();
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        tokens.push((start, end));
+    }
+    tokens
+}
+
+/// Longest-common-subsequence over two token sequences; marks which tokens on each side are common.
+fn lcs_mask(a: &[&str], b: &[&str]) -> (Vec<bool>, Vec<bool>) {
+    let (n, m) = (a.len(), b.len());
+    let mut dp = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            dp[i][j] = if a[i] == b[j] {
+                dp[i + 1][j + 1] + 1
+            } else {
+                dp[i + 1][j].max(dp[i][j + 1])
+            };
+        }
+    }
+
+    let mut a_mask = vec![false; n];
+    let mut b_mask = vec![false; m];
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if a[i] == b[j] {
+            a_mask[i] = true;
+            b_mask[j] = true;
+            i += 1;
+            j += 1;
+        } else if dp[i + 1][j] >= dp[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    (a_mask, b_mask)
+}
+
+/// Build per-byte-range background spans: common tokens get `normal_bg`, others `emph_bg`.
+fn build_emphasis_spans(
+    tokens: &[(usize, usize)],
+    common_mask: &[bool],
+    normal_bg: Color,
+    emph_bg: Color,
+) -> Vec<(usize, usize, Color)> {
+    tokens
+        .iter()
+        .zip(common_mask.iter())
+        .map(|(&(start, end), &is_common)| {
+            (start, end, if is_common { normal_bg } else { emph_bg })
+        })
+        .collect()
+}
+
+/// Word-level diff highlight for a paired removed/added line, falling back to flat highlighting if nothing's common.
+fn format_word_diff_pair(
+    minus_line: &str,
+    plus_line: &str,
+    syntax: &SyntaxReference,
+    theme: &syntect::highlighting::Theme,
+    syntax_set: &SyntaxSet,
+    colors: &Colors,
+    color_mode: ColorMode,
+) -> (String, String) {
+    let minus_tokens = tokenize(minus_line);
+    let plus_tokens = tokenize(plus_line);
+    let minus_strs: Vec<&str> = minus_tokens.iter().map(|&(s, e)| &minus_line[s..e]).collect();
+    let plus_strs: Vec<&str> = plus_tokens.iter().map(|&(s, e)| &plus_line[s..e]).collect();
+    let (minus_mask, plus_mask) = lcs_mask(&minus_strs, &plus_strs);
+
+    if !minus_mask.iter().any(|&is_common| is_common) {
+        return (
+            highlight_line(minus_line, syntax, theme, syntax_set, Some(colors.minus), color_mode),
+            highlight_line(plus_line, syntax, theme, syntax_set, Some(colors.plus), color_mode),
+        );
+    }
+
+    let minus_spans = build_emphasis_spans(&minus_tokens, &minus_mask, colors.minus, colors.minus_emph);
+    let plus_spans = build_emphasis_spans(&plus_tokens, &plus_mask, colors.plus, colors.plus_emph);
+    (
+        highlight_line_with_spans(minus_line, syntax, theme, syntax_set, &minus_spans, colors.minus, color_mode),
+        highlight_line_with_spans(plus_line, syntax, theme, syntax_set, &plus_spans, colors.plus, color_mode),
+    )
+}
+
+/// Highlight a line with syntect foregrounds and a background that varies per byte range per `spans`.
+fn highlight_line_with_spans(
+    line: &str,
+    syntax: &SyntaxReference,
+    theme: &syntect::highlighting::Theme,
+    syntax_set: &SyntaxSet,
+    spans: &[(usize, usize, Color)],
+    normal_bg: Color,
+    color_mode: ColorMode,
+) -> String {
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    let padded_line = pad_line(line);
+    let ranges: Vec<(Style, &str)> = highlighter.highlight(&padded_line, syntax_set);
+
+    let mut buf = String::new();
+    if color_mode == ColorMode::None {
+        for &(_, text) in ranges.iter() {
+            buf.push_str(text);
+        }
+        return buf;
+    }
+
+    let mut span_idx = 0;
+    let mut pos = 0;
+    for (style, text) in ranges {
+        let mut remaining = text;
+        while !remaining.is_empty() {
+            let bg = if pos < line.len() {
+                while span_idx < spans.len() && spans[span_idx].1 <= pos {
+                    span_idx += 1;
+                }
+                spans
+                    .get(span_idx)
+                    .filter(|span| span.0 <= pos)
+                    .map(|span| span.2)
+                    .unwrap_or(normal_bg)
+            } else {
+                normal_bg
+            };
+            let span_end = if pos < line.len() {
+                spans.get(span_idx).map(|span| span.1).unwrap_or(line.len())
+            } else {
+                padded_line.len()
+            };
+            let take = (span_end.saturating_sub(pos)).max(1).min(remaining.len());
+            let (chunk, rest) = remaining.split_at(take);
+            paint(chunk, Some(style.foreground), Some(bg), color_mode, false, &mut buf);
+            remaining = rest;
+            pos += take;
+        }
+    }
+    buf.push_str("\x1b[0m");
+    buf
+}
+
+/// Print every available theme's name with a highlighted sample diff, for `--list-themes`.
+fn list_themes(
+    syntax_set: &SyntaxSet,
+    theme_set: &ThemeSet,
+    color_mode: ColorMode,
+) -> std::io::Result<()> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let syntax = syntax_set
+        .find_syntax_by_extension("rs")
+        .unwrap_or_else(|| syntax_set.find_syntax_plain_text());
+    let sample = ["fn main() {", "-    println!(\"old\");", "+    println!(\"new\");", "}"];
+
+    let mut theme_names: Vec<&String> = theme_set.themes.keys().collect();
+    theme_names.sort();
+
+    for theme_name in theme_names {
+        writeln!(stdout, "{}", theme_name)?;
+        let theme = &theme_set.themes[theme_name];
+        let colors = get_colors(theme_is_light(theme));
+        let mut output = String::new();
+        for line in sample.iter() {
+            let mut highlighter = HighlightLines::new(syntax, theme);
+            let first_char = line.chars().next();
+            let background_color = match first_char {
+                Some('+') => Some(colors.plus),
+                Some('-') => Some(colors.minus),
+                _ => None,
+            };
+            let line = if first_char == Some('+') || first_char == Some('-') {
+                &line[1..]
+            } else {
+                line
+            };
+            let ranges: Vec<(Style, &str)> = highlighter.highlight(line, syntax_set);
+            paint_ranges(&ranges[..], background_color, color_mode, &mut output);
+            writeln!(stdout, "  {}", output)?;
+            output.truncate(0);
+        }
+        writeln!(stdout)?;
     }
     Ok(())
 }
@@ -131,32 +809,45 @@ fn delta() -> std::io::Result<()> {
 fn paint_ranges(
     foreground_style_ranges: &[(Style, &str)],
     background_color: Option<Color>,
+    color_mode: ColorMode,
     buf: &mut String,
 ) -> () {
+    if color_mode == ColorMode::None {
+        for &(_, text) in foreground_style_ranges.iter() {
+            buf.push_str(text);
+        }
+        return;
+    }
     for &(ref style, text) in foreground_style_ranges.iter() {
-        paint(text, Some(style.foreground), background_color, false, buf);
+        paint(text, Some(style.foreground), background_color, color_mode, false, buf);
     }
     buf.push_str("\x1b[0m");
 }
 
-/// Write text to buffer with color escape codes applied.
+/// Write text to buffer with color escape codes applied, per `color_mode`.
 fn paint(
     text: &str,
     foreground_color: Option<Color>,
     background_color: Option<Color>,
+    color_mode: ColorMode,
     reset_color: bool,
     buf: &mut String,
 ) -> () {
     use std::fmt::Write;
+    if color_mode == ColorMode::None {
+        buf.push_str(text);
+        return;
+    }
     match background_color {
         Some(background_color) => {
-            write!(
-                buf,
-                "\x1b[48;2;{};{};{}m",
-                background_color.r,
-                background_color.g,
-                background_color.b
-            ).unwrap();
+            match color_mode {
+                ColorMode::TrueColor => write!(
+                    buf,
+                    "\x1b[48;2;{};{};{}m",
+                    background_color.r, background_color.g, background_color.b
+                ).unwrap(),
+                _ => write!(buf, "\x1b[48;5;{}m", downsample_to_256(background_color)).unwrap(),
+            }
             if reset_color {
                 buf.push_str("\x1b[0m");
             }
@@ -165,14 +856,19 @@ fn paint(
     }
     match foreground_color {
         Some(foreground_color) => {
-            write!(
-                buf,
-                "\x1b[38;2;{};{};{}m{}",
-                foreground_color.r,
-                foreground_color.g,
-                foreground_color.b,
-                text
-            ).unwrap();
+            match color_mode {
+                ColorMode::TrueColor => write!(
+                    buf,
+                    "\x1b[38;2;{};{};{}m{}",
+                    foreground_color.r, foreground_color.g, foreground_color.b, text
+                ).unwrap(),
+                _ => write!(
+                    buf,
+                    "\x1b[38;5;{}m{}",
+                    downsample_to_256(foreground_color),
+                    text
+                ).unwrap(),
+            }
             if reset_color {
                 buf.push_str("\x1b[0m");
             }
@@ -182,3 +878,74 @@ fn paint(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downsample_to_256_does_not_overflow_on_pure_channels() {
+        let white = Color {
+            r: 255,
+            g: 255,
+            b: 255,
+            a: 0,
+        };
+        assert_eq!(downsample_to_256(white), 231);
+
+        let red = Color {
+            r: 255,
+            g: 0,
+            b: 0,
+            a: 0,
+        };
+        assert_eq!(downsample_to_256(red), 196);
+    }
+
+    #[test]
+    fn downsample_to_256_maps_black_to_cube_origin() {
+        let black = Color {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: 0,
+        };
+        assert_eq!(downsample_to_256(black), 16);
+    }
+
+    #[test]
+    fn downsample_to_256_maps_mid_gray_to_grayscale_ramp() {
+        let gray = Color {
+            r: 128,
+            g: 128,
+            b: 128,
+            a: 0,
+        };
+        assert_eq!(downsample_to_256(gray), 244);
+    }
+
+    #[test]
+    fn tokenize_splits_word_and_non_word_runs() {
+        let tokens = tokenize("foo, bar!");
+        let strs: Vec<&str> = tokens.iter().map(|&(s, e)| &"foo, bar!"[s..e]).collect();
+        assert_eq!(strs, vec!["foo", ", ", "bar", "!"]);
+    }
+
+    #[test]
+    fn lcs_mask_marks_shared_tokens_as_common() {
+        let a = vec!["foo", " ", "bar"];
+        let b = vec!["foo", " ", "baz"];
+        let (a_mask, b_mask) = lcs_mask(&a, &b);
+        assert_eq!(a_mask, vec![true, true, false]);
+        assert_eq!(b_mask, vec![true, true, false]);
+    }
+
+    #[test]
+    fn lcs_mask_is_all_false_when_nothing_is_shared() {
+        let a = vec!["foo"];
+        let b = vec!["bar"];
+        let (a_mask, b_mask) = lcs_mask(&a, &b);
+        assert_eq!(a_mask, vec![false]);
+        assert_eq!(b_mask, vec![false]);
+    }
+}
@@ -0,0 +1,108 @@
+/// Return the base name of the file being diffed from a `diff --git a/... b/...` header line.
+pub fn get_file_name_from_diff_line(line: &str) -> Option<&str> {
+    line.split(' ').last().map(|path| {
+        let path = path
+            .trim_start_matches("a/")
+            .trim_start_matches("b/");
+        path.rsplit('/').next().unwrap_or(path)
+    })
+}
+
+/// Return the extension of a file name, if it has one; dotfiles like `.gitignore` have none.
+pub fn get_extension(file_name: &str) -> Option<&str> {
+    let trimmed = file_name.trim_start_matches('.');
+    let extension = trimmed.rsplit('.').next().unwrap_or("");
+    if extension.is_empty() || extension == trimmed {
+        None
+    } else {
+        Some(extension)
+    }
+}
+
+/// Parse a `@@ -old_start,old_len +new_start,new_len @@` hunk header.
+pub fn parse_hunk_header(line: &str) -> Option<(usize, usize, usize, usize)> {
+    let ranges = line.trim_start_matches("@@").splitn(2, "@@").next()?;
+    let mut ranges = ranges.split_whitespace();
+    let old_range = ranges.next()?;
+    let new_range = ranges.next()?;
+    let (old_start, old_len) = parse_range(old_range)?;
+    let (new_start, new_len) = parse_range(new_range)?;
+    Some((old_start, old_len, new_start, new_len))
+}
+
+/// Parse one half of a hunk header, e.g. `-12,7` or `+8`.
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let range = range.trim_start_matches(|c| c == '+' || c == '-');
+    let mut parts = range.splitn(2, ',');
+    let start = parts.next()?.parse().ok()?;
+    let len = match parts.next() {
+        Some(len) => len.parse().ok()?,
+        None => 1,
+    };
+    Some((start, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_range_defaults_len_to_one_when_omitted() {
+        assert_eq!(parse_range("+8"), Some((8, 1)));
+    }
+
+    #[test]
+    fn parse_range_reads_explicit_len() {
+        assert_eq!(parse_range("-12,7"), Some((12, 7)));
+    }
+
+    #[test]
+    fn parse_hunk_header_reads_both_ranges() {
+        assert_eq!(parse_hunk_header("@@ -12,7 +8,3 @@"), Some((12, 7, 8, 3)));
+    }
+
+    #[test]
+    fn parse_hunk_header_ignores_trailing_function_context() {
+        assert_eq!(
+            parse_hunk_header("@@ -12,7 +8,3 @@ fn some_function() {"),
+            Some((12, 7, 8, 3))
+        );
+    }
+
+    #[test]
+    fn get_file_name_from_diff_line_strips_a_b_prefix() {
+        assert_eq!(
+            get_file_name_from_diff_line("diff --git a/src/main.rs b/src/main.rs"),
+            Some("main.rs")
+        );
+    }
+
+    #[test]
+    fn get_file_name_from_diff_line_keeps_extensionless_names() {
+        assert_eq!(
+            get_file_name_from_diff_line("diff --git a/Dockerfile b/Dockerfile"),
+            Some("Dockerfile")
+        );
+    }
+
+    #[test]
+    fn get_extension_reads_the_last_component() {
+        assert_eq!(get_extension("main.rs"), Some("rs"));
+        assert_eq!(get_extension("archive.tar.gz"), Some("gz"));
+    }
+
+    #[test]
+    fn get_extension_treats_dotfiles_as_extensionless() {
+        assert_eq!(get_extension(".gitignore"), None);
+    }
+
+    #[test]
+    fn get_extension_finds_an_extension_after_a_leading_dot() {
+        assert_eq!(get_extension(".eslintrc.json"), Some("json"));
+    }
+
+    #[test]
+    fn get_extension_is_none_without_a_dot() {
+        assert_eq!(get_extension("Dockerfile"), None);
+    }
+}